@@ -0,0 +1,305 @@
+//! UCAN-style off-log capability delegation, anchored on-log via
+//! `CapabilitySeal` (see `event::sections::seal`).
+//!
+//! A `CapabilityToken` grants `audience` a set of capabilities, optionally
+//! attenuated from a `proof` token. Only its digest is anchored on-log, via
+//! a `CapabilitySeal` carried in an `InteractionEvent`; `verify` walks the
+//! proof chain checking signatures, anchoring, and attenuation at each hop.
+
+use crate::derivation::self_addressing::SelfAddressing;
+use crate::error::Error;
+use crate::event::event_data::EventData;
+use crate::event::sections::seal::{CapabilitySeal, Seal};
+use crate::event_message::parse::{signed_event_stream, signed_event_stream_validate, Deserialized};
+use crate::prefix::{AttachedSignaturePrefix, IdentifierPrefix, SelfAddressingPrefix};
+use crate::state::IdentifierState;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use std::cell::Cell;
+
+/// A single `(resource, ability)` pair a capability token grants.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Capability {
+    pub resource: String,
+    pub ability: String,
+}
+
+/// Longest proof chain `verify` will walk before giving up.
+const MAX_PROOF_CHAIN_DEPTH: usize = 32;
+
+/// A signed, UCAN-style capability token. `issuer` grants `audience` the
+/// listed `capabilities`, optionally attenuated from a `proof` token issued
+/// to `issuer` by some ancestor.
+#[derive(Serialize, Debug, Clone)]
+pub struct CapabilityToken {
+    pub issuer: IdentifierPrefix,
+    pub audience: IdentifierPrefix,
+    pub capabilities: Vec<Capability>,
+    pub proof: Option<Box<CapabilityToken>>,
+    pub signature: AttachedSignaturePrefix,
+}
+
+thread_local! {
+    /// Hops deserialized so far on the current thread's call stack. `Box<T>`
+    /// and `Option<T>` both deserialize `T` by calling `T::deserialize`
+    /// straight through, so this counts recursive `CapabilityToken::proof`
+    /// deserialization regardless of which serde format is driving it.
+    static DESERIALIZE_DEPTH: Cell<usize> = Cell::new(0);
+}
+
+/// Mirrors `CapabilityToken`'s fields for the derived field-level
+/// `Deserialize`; `proof` is deserialized through the depth-checked impl
+/// below rather than recursing straight into `derive(Deserialize)`.
+#[derive(Deserialize)]
+struct CapabilityTokenFields {
+    issuer: IdentifierPrefix,
+    audience: IdentifierPrefix,
+    capabilities: Vec<Capability>,
+    proof: Option<Box<CapabilityToken>>,
+    signature: AttachedSignaturePrefix,
+}
+
+impl<'de> Deserialize<'de> for CapabilityToken {
+    /// Rejects a proof chain deeper than `MAX_PROOF_CHAIN_DEPTH` while it is
+    /// still being parsed, rather than only once `verify`/`Drop` later walk
+    /// it: an attacker-supplied `proof` chain could otherwise stack-overflow
+    /// the process during deserialization itself, before any application
+    /// code runs.
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let depth = DESERIALIZE_DEPTH.with(|d| {
+            let depth = d.get() + 1;
+            d.set(depth);
+            depth
+        });
+        struct ResetDepthOnDrop;
+        impl Drop for ResetDepthOnDrop {
+            fn drop(&mut self) {
+                DESERIALIZE_DEPTH.with(|d| d.set(d.get() - 1));
+            }
+        }
+        let _reset = ResetDepthOnDrop;
+
+        if depth > MAX_PROOF_CHAIN_DEPTH {
+            return Err(de::Error::custom(format!(
+                "capability token proof chain exceeds the maximum of {} hops",
+                MAX_PROOF_CHAIN_DEPTH
+            )));
+        }
+
+        CapabilityTokenFields::deserialize(deserializer).map(|f| CapabilityToken {
+            issuer: f.issuer,
+            audience: f.audience,
+            capabilities: f.capabilities,
+            proof: f.proof,
+            signature: f.signature,
+        })
+    }
+}
+
+/// Tears the chain down one hop at a time instead of relying on recursive
+/// drop glue, which a deep enough chain would stack-overflow.
+impl Drop for CapabilityToken {
+    fn drop(&mut self) {
+        let mut next = self.proof.take();
+        while let Some(mut token) = next {
+            next = token.proof.take();
+        }
+    }
+}
+
+impl CapabilityToken {
+    /// Bytes the token's signature is computed over: everything but the
+    /// signature itself.
+    fn signing_payload(&self) -> Result<Vec<u8>, Error> {
+        #[derive(Serialize)]
+        struct Unsigned<'a> {
+            issuer: &'a IdentifierPrefix,
+            audience: &'a IdentifierPrefix,
+            capabilities: &'a [Capability],
+            proof: &'a Option<Box<CapabilityToken>>,
+        }
+        serde_json::to_vec(&Unsigned {
+            issuer: &self.issuer,
+            audience: &self.audience,
+            capabilities: &self.capabilities,
+            proof: &self.proof,
+        })
+        .map_err(|e| Error::SemanticError(format!("could not serialize capability token: {}", e)))
+    }
+
+    /// Digest anchored on-log by the issuer via a `CapabilitySeal`.
+    pub fn digest(&self) -> Result<SelfAddressingPrefix, Error> {
+        Ok(SelfAddressing::Blake3_256.derive(&self.signing_payload()?))
+    }
+
+    /// The `Seal` the issuer should carry in an interaction event to commit
+    /// to having issued this token.
+    pub fn seal(&self) -> Result<Seal, Error> {
+        Ok(Seal::Capability(CapabilitySeal {
+            dig: self.digest()?,
+        }))
+    }
+
+    /// Verify this token and its entire proof chain. `key_state_of` resolves
+    /// an issuer's current `IdentifierState` and `anchored_in_log` reports
+    /// whether a digest appears as a `CapabilitySeal` in that issuer's log
+    /// (see `key_state_from_kel`/`anchored_in_kel` for KEL-backed
+    /// implementations of both). Walks the chain iteratively and gives up
+    /// past `MAX_PROOF_CHAIN_DEPTH` hops.
+    pub fn verify<F, G>(&self, key_state_of: F, anchored_in_log: G) -> Result<(), Error>
+    where
+        F: Fn(&IdentifierPrefix) -> Result<IdentifierState, Error>,
+        G: Fn(&IdentifierPrefix, &SelfAddressingPrefix) -> Result<bool, Error>,
+    {
+        let mut current = self;
+        for _ in 0..MAX_PROOF_CHAIN_DEPTH {
+            let state = key_state_of(&current.issuer)?;
+            let payload = current.signing_payload()?;
+            let verifies = state
+                .current
+                .verify(&payload, std::slice::from_ref(&current.signature))?;
+            if !verifies {
+                return Err(Error::SemanticError(format!(
+                    "capability token issued by {} does not verify under its current keys",
+                    current.issuer
+                )));
+            }
+
+            let digest = current.digest()?;
+            if !anchored_in_log(&current.issuer, &digest)? {
+                return Err(Error::SemanticError(format!(
+                    "capability token issued by {} is not anchored in its issuer's log",
+                    current.issuer
+                )));
+            }
+
+            current = match &current.proof {
+                None => return Ok(()),
+                Some(parent) => {
+                    if parent.audience != current.issuer {
+                        return Err(Error::SemanticError(
+                            "proof token's audience does not match the delegated token's issuer"
+                                .into(),
+                        ));
+                    }
+                    if !attenuates(&current.capabilities, &parent.capabilities) {
+                        return Err(Error::SemanticError(format!(
+                            "capability token issued by {} escalates beyond its proof",
+                            current.issuer
+                        )));
+                    }
+                    parent
+                }
+            };
+        }
+
+        Err(Error::SemanticError(format!(
+            "capability token proof chain exceeds the maximum of {} hops",
+            MAX_PROOF_CHAIN_DEPTH
+        )))
+    }
+}
+
+/// `granted` attenuates `parent` iff every capability in `granted` also
+/// appears in `parent` — a delegated token may only narrow, never widen,
+/// the capabilities it was handed.
+fn attenuates(granted: &[Capability], parent: &[Capability]) -> bool {
+    granted.iter().all(|c| parent.contains(c))
+}
+
+/// `key_state_of` backed by an issuer's raw key event log, run through the
+/// real stream validator.
+pub fn key_state_from_kel(kel: &[u8]) -> Result<IdentifierState, Error> {
+    signed_event_stream_validate(kel)
+        .map(|(_, state)| state)
+        .map_err(|e| Error::SemanticError(format!("could not validate key event log: {:?}", e)))
+}
+
+/// `anchored_in_log` backed by an issuer's raw key event log: does any of
+/// its interaction events anchor `digest` via a `CapabilitySeal`?
+pub fn anchored_in_kel(
+    kel: &[u8],
+    issuer: &IdentifierPrefix,
+    digest: &SelfAddressingPrefix,
+) -> Result<bool, Error> {
+    let (_, events) = signed_event_stream(kel)
+        .map_err(|e| Error::SemanticError(format!("could not parse key event log: {:?}", e)))?;
+    Ok(events.iter().any(|d| match d {
+        Deserialized::Event(e)
+            if &e.event.event.event.prefix == issuer
+                && matches!(&e.event.event.event.event_data, EventData::Ixn(ixn) if ixn.anchors_capability(digest)) =>
+        {
+            true
+        }
+        _ => false,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cap(resource: &str, ability: &str) -> Capability {
+        Capability {
+            resource: resource.into(),
+            ability: ability.into(),
+        }
+    }
+
+    #[test]
+    fn test_attenuates_subset_only() {
+        let parent = vec![cap("drive:1", "read"), cap("drive:1", "write")];
+        let equal = parent.clone();
+        let narrowed = vec![cap("drive:1", "read")];
+        let escalated = vec![cap("drive:1", "read"), cap("drive:1", "admin")];
+
+        assert!(attenuates(&equal, &parent));
+        assert!(attenuates(&narrowed, &parent));
+        assert!(!attenuates(&escalated, &parent));
+    }
+
+    fn root_token() -> CapabilityToken {
+        use crate::derivation::self_signing::SelfSigning;
+        use std::str::FromStr;
+
+        CapabilityToken {
+            issuer: IdentifierPrefix::from_str(
+                "EAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+            )
+            .unwrap(),
+            audience: IdentifierPrefix::from_str(
+                "EAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+            )
+            .unwrap(),
+            capabilities: vec![],
+            proof: None,
+            signature: AttachedSignaturePrefix::new(SelfSigning::Ed25519Sha512, vec![0u8; 64], 0),
+        }
+    }
+
+    fn chain_of(depth: usize) -> CapabilityToken {
+        let mut token = root_token();
+        for _ in 0..depth {
+            let mut next = root_token();
+            next.proof = Some(Box::new(token));
+            token = next;
+        }
+        token
+    }
+
+    #[test]
+    fn test_proof_chain_within_limit_round_trips() {
+        let token = chain_of(MAX_PROOF_CHAIN_DEPTH - 1);
+        let serialized = serde_json::to_string(&token).unwrap();
+        assert!(serde_json::from_str::<CapabilityToken>(&serialized).is_ok());
+    }
+
+    #[test]
+    fn test_proof_chain_deeper_than_limit_fails_to_deserialize() {
+        let token = chain_of(MAX_PROOF_CHAIN_DEPTH);
+        let serialized = serde_json::to_string(&token).unwrap();
+        assert!(serde_json::from_str::<CapabilityToken>(&serialized).is_err());
+    }
+}
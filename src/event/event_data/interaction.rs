@@ -19,6 +19,16 @@ impl EventSemantics for InteractionEvent {
     }
 }
 
+impl InteractionEvent {
+    /// Whether this event anchors `digest` via a `Seal::Capability` (see
+    /// `crate::delegation::CapabilityToken::verify`'s `anchored_in_log` hook).
+    pub fn anchors_capability(&self, digest: &SelfAddressingPrefix) -> bool {
+        self.data
+            .iter()
+            .any(|seal| matches!(seal, Seal::Capability(s) if &s.dig == digest))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -56,4 +66,23 @@ mod tests {
             _ => assert!(false),
         }
     }
+
+    #[test]
+    fn test_anchors_capability_matches_only_its_own_digest() {
+        let anchored = SelfAddressing::Blake3_256.derive(b"capability token bytes");
+        let other = SelfAddressing::Blake3_256.derive(b"some other digest");
+
+        let ixn = InteractionEvent {
+            previous_event_hash: SelfAddressingPrefix::from_str(
+                "EAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+            )
+            .unwrap(),
+            data: vec![seal::Seal::Capability(seal::CapabilitySeal {
+                dig: anchored.clone(),
+            })],
+        };
+
+        assert!(ixn.anchors_capability(&anchored));
+        assert!(!ixn.anchors_capability(&other));
+    }
 }
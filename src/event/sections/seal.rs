@@ -0,0 +1,53 @@
+use crate::prefix::SelfAddressingPrefix;
+use serde::{Deserialize, Serialize};
+
+/// Seals are anchors: cryptographic commitments to external data recorded
+/// in an interaction event so the data can later be cited and checked
+/// against the log.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Seal {
+    Digest(DigestSeal),
+    Capability(CapabilitySeal),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DigestSeal {
+    pub dig: SelfAddressingPrefix,
+}
+
+/// Anchors the digest of a UCAN-style capability token (see
+/// `crate::delegation::CapabilityToken`) issued off-log by this identifier.
+/// A verifier holding the token out-of-band confirms it was actually issued
+/// by recomputing its digest and matching it against this seal in the
+/// issuer's log.
+///
+/// Serialized under the `cd` key rather than `dig`: `Seal` is untagged, and
+/// since serde picks the first variant whose required fields are all
+/// present, a `CapabilitySeal` sharing `DigestSeal`'s `dig` key would always
+/// round-trip back as `Seal::Digest`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CapabilitySeal {
+    #[serde(rename = "cd")]
+    pub dig: SelfAddressingPrefix,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::derivation::self_addressing::SelfAddressing;
+
+    #[test]
+    fn test_capability_seal_round_trips_as_capability() {
+        let dig = SelfAddressing::Blake3_256.derive(b"capability token bytes");
+        let seal = Seal::Capability(CapabilitySeal { dig: dig.clone() });
+
+        let serialized = serde_json::to_string(&seal).unwrap();
+        let deserialized: Seal = serde_json::from_str(&serialized).unwrap();
+
+        match deserialized {
+            Seal::Capability(s) => assert_eq!(s.dig, dig),
+            Seal::Digest(_) => panic!("capability seal round-tripped as a digest seal"),
+        }
+    }
+}
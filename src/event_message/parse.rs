@@ -11,7 +11,7 @@ use crate::{
     },
     state::IdentifierState,
 };
-use nom::{branch::*, combinator::*, error::ErrorKind, multi::*, sequence::*};
+use nom::{combinator::*, error::ErrorKind, multi::*, sequence::*};
 use rmp_serde as serde_mgpk;
 use serde::{Deserialize, Deserializer, Serialize};
 use std::io::Cursor;
@@ -86,8 +86,77 @@ fn mgpk_message(s: &[u8]) -> nom::IResult<&[u8], DeserializedEvent> {
     }
 }
 
+/// Byte length of a version string, e.g. `KERI10JSON00011c_`: 4-byte
+/// protocol tag, 2-byte protocol version, 4-byte encoding tag, 6 hex digits
+/// of declared size, trailing `_`.
+const VERSION_STRING_LEN: usize = 17;
+/// The `v` field is required to be serialized first, so the version string
+/// always appears within this many bytes of the start regardless of which
+/// of the three encodings framed it (binary map/array headers are short).
+const VERSION_STRING_LOOKAHEAD: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Json,
+    Cbor,
+    MsgPack,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct VersionInfo {
+    encoding: Encoding,
+    /// Declared size of the serialized event, in bytes, from the version
+    /// string's 6 hex digits.
+    size: usize,
+}
+
+/// Peek the `v` field's version string out of the leading bytes without
+/// consuming them, so `message` can dispatch straight to the matching
+/// decoder instead of trying all three in turn.
+fn version_string(s: &[u8]) -> nom::IResult<&[u8], VersionInfo> {
+    if s.len() < VERSION_STRING_LEN {
+        return Err(nom::Err::Error((s, ErrorKind::Eof)));
+    }
+    let window = &s[..s.len().min(VERSION_STRING_LOOKAHEAD)];
+    let found = (0..=window.len() - VERSION_STRING_LEN)
+        .map(|start| &window[start..start + VERSION_STRING_LEN])
+        .find_map(|candidate| parse_version_string(candidate));
+
+    match found {
+        Some(info) => Ok((s, info)),
+        None => Err(nom::Err::Error((s, ErrorKind::IsNot))),
+    }
+}
+
+fn parse_version_string(candidate: &[u8]) -> Option<VersionInfo> {
+    if &candidate[0..4] != b"KERI" || candidate[16] != b'_' {
+        return None;
+    }
+    let encoding = match &candidate[6..10] {
+        b"JSON" => Encoding::Json,
+        b"CBOR" => Encoding::Cbor,
+        b"MGPK" => Encoding::MsgPack,
+        _ => return None,
+    };
+    let size_hex = std::str::from_utf8(&candidate[10..16]).ok()?;
+    let size = usize::from_str_radix(size_hex, 16).ok()?;
+    Some(VersionInfo { encoding, size })
+}
+
 pub fn message<'a>(s: &'a [u8]) -> nom::IResult<&[u8], DeserializedEvent> {
-    alt((json_message, cbor_message, mgpk_message))(s).map(|d| (d.0, d.1))
+    let (_, version) = version_string(s)?;
+
+    let (rest, event) = match version.encoding {
+        Encoding::Json => json_message(s)?,
+        Encoding::Cbor => cbor_message(s)?,
+        Encoding::MsgPack => mgpk_message(s)?,
+    };
+
+    if event.raw.len() != version.size {
+        return Err(nom::Err::Error((s, ErrorKind::LengthValue)));
+    }
+
+    Ok((rest, event))
 }
 
 /// extracts the count from the sig count code
@@ -299,3 +368,33 @@ fn test_stream3() {
     let result = signed_event_stream_validate(stream);
     assert!(!result.is_ok());
 }
+
+#[test]
+fn test_version_string_size_mismatch() {
+    // Declared size (0000e6) is shorter than the actual inception event that
+    // follows it, so `message` must reject the frame instead of silently
+    // accepting a truncated/overlong one.
+    let stream = br#"{"v":"KERI10JSON0000e6_","i":"DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA","s":"0","t":"icp","kt":"1","k":["DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA","extra_padding_to_throw_off_the_declared_size"],"n":"EPYuj8mq_PYYsoBKkzX1kxSPGYBWaIya3slgCOyOtlqU","wt":"0","w":[],"c":[]}"#;
+
+    assert!(message(stream).is_err());
+}
+
+#[test]
+fn test_version_string_dispatches_without_trying_other_encodings() {
+    // The peeked version string alone identifies the encoding and frame size,
+    // so `message` can dispatch straight to `json_message` instead of
+    // trial-and-erroring through `cbor_message`/`mgpk_message` first.
+    let stream = br#"{"v":"KERI10JSON000098_","i":"DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA","s":"3","t":"ixn","p":"EHBaMkc2lTj-1qnIgSeD0GmYjw8Zv6EmCgGDVPedn3fI","a":[]}"#;
+    let (_, info) = version_string(stream).unwrap();
+    assert_eq!(info.encoding, Encoding::Json);
+    assert_eq!(info.size, stream.len());
+}
+
+#[test]
+fn test_version_string_short_input_does_not_panic() {
+    // Truncated/corrupted trailing bytes shorter than a version string must
+    // be rejected, not slice-panic `version_string`/`message`.
+    assert!(version_string(b"").is_err());
+    assert!(version_string(b"{}").is_err());
+    assert!(message(b"{}").is_err());
+}
@@ -1,15 +1,25 @@
+// LMDB is a native dependency and doesn't target wasm32; the parser/verifier
+// in `event_message` stay reachable without it so the `wasm` bindings below
+// can compile for the browser.
+#[cfg(not(target_arch = "wasm32"))]
 pub mod database;
+pub mod delegation;
 pub mod derivation;
 pub mod error;
 pub mod event;
 pub mod event_message;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod controller;
 pub mod log;
 pub mod prefix;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod processor;
 pub mod signer;
 pub mod state;
 pub mod util;
 
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
 #[cfg(feature = "exp_ursa")]
 pub use ursa;
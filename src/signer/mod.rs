@@ -1,33 +1,140 @@
+mod slip10;
+
+use crate::derivation::basic::Basic;
+use crate::derivation::self_signing::SelfSigning;
 use crate::error::Error;
+use crate::prefix::{AttachedSignaturePrefix, BasicPrefix, SeedPrefix};
+use std::str::FromStr;
 use ursa::{
-    keys::{PrivateKey, PublicKey},
-    signatures::{ed25519, SignatureScheme},
+    keys::{KeyGenOption, PrivateKey, PublicKey},
+    signatures::{ed25519, secp256k1, SignatureScheme},
 };
 
+/// Signature scheme a `KeyManager` is operating under.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyType {
+    Ed25519,
+    ECDSAsecp256k1,
+    Ed448,
+}
+
+impl KeyType {
+    /// `SelfSigning` code a signature under this scheme should be tagged with.
+    pub fn self_signing(&self) -> SelfSigning {
+        match self {
+            KeyType::Ed25519 => SelfSigning::Ed25519Sha512,
+            KeyType::ECDSAsecp256k1 => SelfSigning::ECDSAsecp256k1Sha256,
+            KeyType::Ed448 => SelfSigning::Ed448,
+        }
+    }
+
+    /// `Basic` derivation code a public key under this scheme should be tagged with.
+    pub fn basic(&self) -> Basic {
+        match self {
+            KeyType::Ed25519 => Basic::Ed25519,
+            KeyType::ECDSAsecp256k1 => Basic::ECDSAsecp256k1,
+            KeyType::Ed448 => Basic::Ed448,
+        }
+    }
+}
+
 pub trait KeyManager {
     fn sign(&self, msg: &Vec<u8>) -> Result<Vec<u8>, Error>;
-    fn public_key(&self) -> PublicKey;
-    fn next_pub_key(&self) -> PublicKey;
+    /// Current public key, tagged with this key manager's `Basic` code.
+    fn public_key(&self) -> BasicPrefix;
+    /// Pre-rotated next public key, tagged the same way as `public_key`.
+    fn next_pub_key(&self) -> BasicPrefix;
     fn rotate(&self) -> Result<Self, Error>
     where
         Self: Sized;
+    /// Scheme this key manager signs and derives keys under.
+    fn key_type(&self) -> KeyType;
+}
+
+/// SLIP-0010 derivation path of a `CryptoBox` built with `from_seed`, so
+/// `rotate` can advance it deterministically instead of drawing randomness.
+#[derive(Clone)]
+struct HdChain {
+    seed: Vec<u8>,
+    path: Vec<u32>,
+    index: u32,
+}
+
+impl HdChain {
+    fn key_at(&self, index: u32) -> Result<PrivateKey, Error> {
+        let mut path = self.path.clone();
+        path.push(index);
+        Ok(PrivateKey(slip10::derive(&self.seed, &path)?.to_vec()))
+    }
 }
 
 pub struct CryptoBox {
     signer: Signer,
     next_priv_key: PrivateKey,
     next_pub_key: PublicKey,
+    key_type: KeyType,
+    hd_chain: Option<HdChain>,
 }
 
 impl CryptoBox {
+    /// Random Ed25519 key pair, matching the crate's historical default.
     pub fn new() -> Result<Self, Error> {
-        let ed = ed25519::Ed25519Sha512::new();
-        let signer = Signer::new()?;
-        let (next_pub_key, next_priv_key) = ed.keypair(None).map_err(|e| Error::CryptoError(e))?;
+        Self::new_with_scheme(KeyType::Ed25519)
+    }
+
+    /// Random key pair generated under the chosen scheme.
+    pub fn new_with_scheme(key_type: KeyType) -> Result<Self, Error> {
+        let signer = Signer::new(key_type)?;
+        let (next_pub_key, next_priv_key) = generate_keypair(key_type, None)?;
         Ok(CryptoBox {
             signer,
             next_pub_key,
             next_priv_key,
+            key_type,
+            hd_chain: None,
+        })
+    }
+
+    /// Current and next key pair taken directly from two qb64 Ed25519 seeds,
+    /// bypassing random generation.
+    pub fn derive_from_seed(current_seed: &str, next_seed: &str) -> Result<Self, Error> {
+        let (pub_key, priv_key) = keypair_from_seed(current_seed)?;
+        let (next_pub_key, next_priv_key) = keypair_from_seed(next_seed)?;
+        Ok(CryptoBox {
+            signer: Signer {
+                pub_key,
+                priv_key,
+                key_type: KeyType::Ed25519,
+            },
+            next_pub_key,
+            next_priv_key,
+            key_type: KeyType::Ed25519,
+            hd_chain: None,
+        })
+    }
+
+    /// `CryptoBox` whose rotation chain is derived from `seed` via SLIP-0010
+    /// hardened derivation rooted at `path`: current key is `path || [0]`,
+    /// next is `path || [1]`, and `rotate()` walks the chain one index at a
+    /// time from there.
+    pub fn from_seed(seed: &[u8], path: Vec<u32>) -> Result<Self, Error> {
+        let hd_chain = HdChain {
+            seed: seed.to_vec(),
+            path,
+            index: 0,
+        };
+        let (pub_key, priv_key) = ed25519_keypair_from_priv(hd_chain.key_at(0)?)?;
+        let (next_pub_key, next_priv_key) = ed25519_keypair_from_priv(hd_chain.key_at(1)?)?;
+        Ok(CryptoBox {
+            signer: Signer {
+                pub_key,
+                priv_key,
+                key_type: KeyType::Ed25519,
+            },
+            next_pub_key,
+            next_priv_key,
+            key_type: KeyType::Ed25519,
+            hd_chain: Some(hd_chain),
         })
     }
 }
@@ -37,47 +144,245 @@ impl KeyManager for CryptoBox {
         self.signer.sign(msg)
     }
 
-    fn public_key(&self) -> PublicKey {
-        self.signer.pub_key.clone()
+    fn public_key(&self) -> BasicPrefix {
+        BasicPrefix::new(self.key_type().basic(), self.signer.pub_key.clone())
     }
 
     fn rotate(&self) -> Result<Self, Error> {
-        let ed = ed25519::Ed25519Sha512::new();
-        let (next_pub_key, next_priv_key) = ed.keypair(None).map_err(|e| Error::CryptoError(e))?;
         let new_signer = Signer {
             priv_key: self.next_priv_key.clone(),
             pub_key: self.next_pub_key.clone(),
+            key_type: self.key_type,
+        };
+
+        let (next_pub_key, next_priv_key, hd_chain) = match &self.hd_chain {
+            Some(hd) => {
+                let next_index = hd.index + 2;
+                let (pub_key, priv_key) =
+                    ed25519_keypair_from_priv(hd.key_at(next_index)?)?;
+                let advanced = HdChain {
+                    seed: hd.seed.clone(),
+                    path: hd.path.clone(),
+                    index: hd.index + 1,
+                };
+                (pub_key, priv_key, Some(advanced))
+            }
+            None => {
+                let (pub_key, priv_key) = generate_keypair(self.key_type, None)?;
+                (pub_key, priv_key, None)
+            }
         };
 
         Ok(CryptoBox {
             signer: new_signer,
             next_priv_key,
             next_pub_key,
+            key_type: self.key_type,
+            hd_chain,
         })
     }
 
-    fn next_pub_key(&self) -> PublicKey {
-        self.next_pub_key.clone()
+    fn next_pub_key(&self) -> BasicPrefix {
+        BasicPrefix::new(self.key_type().basic(), self.next_pub_key.clone())
+    }
+
+    fn key_type(&self) -> KeyType {
+        self.key_type
+    }
+}
+
+impl CryptoBox {
+    /// Sign `msg` and wrap the result as an `AttachedSignaturePrefix` tagged
+    /// with this box's `SelfSigning` code, ready to attach at stream index
+    /// `index`.
+    pub fn sign_attached(
+        &self,
+        msg: &Vec<u8>,
+        index: u16,
+    ) -> Result<AttachedSignaturePrefix, Error> {
+        let signature = self.sign(msg)?;
+        Ok(AttachedSignaturePrefix::new(
+            self.key_type().self_signing(),
+            signature,
+            index,
+        ))
     }
 }
 
 struct Signer {
     priv_key: PrivateKey,
     pub pub_key: PublicKey,
+    key_type: KeyType,
 }
 
 impl Signer {
-    pub fn new() -> Result<Self, Error> {
-        let ed = ed25519::Ed25519Sha512::new();
-        let (pub_key, priv_key) = ed.keypair(None).map_err(|e| Error::CryptoError(e))?;
+    pub fn new(key_type: KeyType) -> Result<Self, Error> {
+        let (pub_key, priv_key) = generate_keypair(key_type, None)?;
 
-        Ok(Signer { pub_key, priv_key })
+        Ok(Signer {
+            pub_key,
+            priv_key,
+            key_type,
+        })
     }
 
     fn sign(&self, msg: &Vec<u8>) -> Result<Vec<u8>, Error> {
-        let signature = ed25519::Ed25519Sha512::new()
-            .sign(&msg, &self.priv_key)
-            .map_err(|e| Error::CryptoError(e))?;
-        Ok(signature)
+        match self.key_type {
+            KeyType::Ed25519 => ed25519::Ed25519Sha512::new()
+                .sign(msg, &self.priv_key)
+                .map_err(|e| Error::CryptoError(e)),
+            KeyType::ECDSAsecp256k1 => secp256k1::EcdsaSecp256k1Sha256::new()
+                .sign(msg, &self.priv_key)
+                .map_err(|e| Error::CryptoError(e)),
+            KeyType::Ed448 => ed448::sign(msg, &self.priv_key),
+        }
+    }
+}
+
+/// Decode a qb64 Ed25519 seed prefix and derive its key pair directly,
+/// without going through SLIP-0010 (a single seed is one key, not a chain).
+fn keypair_from_seed(seed: &str) -> Result<(PublicKey, PrivateKey), Error> {
+    let seed_prefix = SeedPrefix::from_str(seed)?;
+    seed_prefix.derive_key_pair()
+}
+
+/// Turn a raw SLIP-0010-derived Ed25519 private key seed into an ursa
+/// keypair.
+fn ed25519_keypair_from_priv(priv_key: PrivateKey) -> Result<(PublicKey, PrivateKey), Error> {
+    ed25519::Ed25519Sha512::new()
+        .keypair(Some(KeyGenOption::FromSecretKey(priv_key)))
+        .map_err(|e| Error::CryptoError(e))
+}
+
+fn generate_keypair(
+    key_type: KeyType,
+    option: Option<KeyGenOption>,
+) -> Result<(PublicKey, PrivateKey), Error> {
+    match key_type {
+        KeyType::Ed25519 => ed25519::Ed25519Sha512::new()
+            .keypair(option)
+            .map_err(|e| Error::CryptoError(e)),
+        KeyType::ECDSAsecp256k1 => secp256k1::EcdsaSecp256k1Sha256::new()
+            .keypair(option)
+            .map_err(|e| Error::CryptoError(e)),
+        KeyType::Ed448 => ed448::keypair(option),
+    }
+}
+
+/// Ed448 is not implemented by `ursa`, so its signing/keygen is delegated to
+/// the `ed448-rust` crate and adapted to `ursa`'s `PrivateKey`/`PublicKey`
+/// newtypes to keep `Signer`/`generate_keypair` scheme-agnostic above.
+mod ed448 {
+    use super::KeyGenOption;
+    use crate::error::Error;
+    use ursa::keys::{PrivateKey, PublicKey};
+
+    pub fn keypair(option: Option<KeyGenOption>) -> Result<(PublicKey, PrivateKey), Error> {
+        let seed = match option {
+            Some(KeyGenOption::UseSeed(seed)) => seed,
+            _ => {
+                use rand::RngCore;
+                let mut seed = vec![0u8; 57];
+                rand::rngs::OsRng.fill_bytes(&mut seed);
+                seed
+            }
+        };
+        let key_pair = ed448_rust::PrivateKey::try_from(seed.as_slice())
+            .map_err(|_| Error::SemanticError("invalid Ed448 seed".into()))?;
+        let public = ed448_rust::PublicKey::from(&key_pair);
+        Ok((
+            PublicKey(public.as_byte().to_vec()),
+            PrivateKey(key_pair.as_bytes().to_vec()),
+        ))
+    }
+
+    pub fn sign(msg: &[u8], priv_key: &PrivateKey) -> Result<Vec<u8>, Error> {
+        let key = ed448_rust::PrivateKey::try_from(priv_key.0.as_slice())
+            .map_err(|_| Error::SemanticError("invalid Ed448 private key".into()))?;
+        key.sign(msg, None)
+            .map(|sig| sig.to_vec())
+            .map_err(|_| Error::SemanticError("Ed448 signing failed".into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_round_trips(key_type: KeyType) {
+        let box_ = CryptoBox::new_with_scheme(key_type).unwrap();
+        let msg = b"test message".to_vec();
+        let sig = box_.sign(&msg).unwrap();
+
+        let verifies = match key_type {
+            KeyType::Ed25519 => ed25519::Ed25519Sha512::new()
+                .verify(&msg, &sig, &box_.signer.pub_key)
+                .unwrap(),
+            KeyType::ECDSAsecp256k1 => secp256k1::EcdsaSecp256k1Sha256::new()
+                .verify(&msg, &sig, &box_.signer.pub_key)
+                .unwrap(),
+            KeyType::Ed448 => {
+                let priv_key =
+                    ed448_rust::PrivateKey::try_from(box_.signer.priv_key.0.as_slice()).unwrap();
+                let pub_key = ed448_rust::PublicKey::from(&priv_key);
+                pub_key.verify(&msg, &sig, None).is_ok()
+            }
+        };
+        assert!(verifies);
+    }
+
+    #[test]
+    fn test_ed25519_sign_verify_round_trips() {
+        assert_round_trips(KeyType::Ed25519);
+    }
+
+    #[test]
+    fn test_ecdsa_secp256k1_sign_verify_round_trips() {
+        assert_round_trips(KeyType::ECDSAsecp256k1);
+    }
+
+    #[test]
+    fn test_ed448_sign_verify_round_trips() {
+        assert_round_trips(KeyType::Ed448);
+    }
+
+    #[test]
+    fn test_public_key_tagged_with_matching_basic_code() {
+        for key_type in [KeyType::Ed25519, KeyType::ECDSAsecp256k1, KeyType::Ed448] {
+            let box_ = CryptoBox::new_with_scheme(key_type).unwrap();
+            assert_eq!(
+                box_.public_key(),
+                BasicPrefix::new(key_type.basic(), box_.signer.pub_key.clone())
+            );
+            assert_eq!(
+                box_.next_pub_key(),
+                BasicPrefix::new(key_type.basic(), box_.next_pub_key.clone())
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_seed_rotate_matches_slip10_derivation() {
+        let seed = [7u8; 32];
+        let path = vec![44, 0];
+
+        let rotated = CryptoBox::from_seed(&seed, path.clone())
+            .unwrap()
+            .rotate()
+            .unwrap();
+
+        let mut current_path = path.clone();
+        current_path.push(1);
+        assert_eq!(
+            rotated.signer.priv_key.0,
+            slip10::derive(&seed, &current_path).unwrap().to_vec()
+        );
+
+        let mut next_path = path;
+        next_path.push(2);
+        assert_eq!(
+            rotated.next_priv_key.0,
+            slip10::derive(&seed, &next_path).unwrap().to_vec()
+        );
     }
 }
@@ -0,0 +1,107 @@
+//! SLIP-0010 hierarchical deterministic derivation, restricted to the
+//! Ed25519 curve where only hardened child keys are defined.
+//! <https://github.com/satoshilabs/slips/blob/master/slip-0010.md>
+
+use crate::error::Error;
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha512;
+
+const HARDENED_OFFSET: u32 = 1 << 31;
+
+type HmacSha512 = Hmac<Sha512>;
+
+/// A derived Ed25519 private key together with the chain code needed to
+/// derive its children.
+#[derive(Clone)]
+pub struct ExtendedKey {
+    pub priv_key: [u8; 32],
+    pub chain_code: [u8; 32],
+}
+
+impl ExtendedKey {
+    /// `I = HMAC-SHA512(key = "ed25519 seed", data = seed)`.
+    pub fn master(seed: &[u8]) -> Self {
+        let mut mac = HmacSha512::new_varkey(b"ed25519 seed").expect("HMAC accepts any key size");
+        mac.update(seed);
+        Self::from_hmac(mac)
+    }
+
+    /// Derive the hardened child at `index`, erroring if `index` is already
+    /// in the hardened range (`>= 2^31`). Ed25519 only defines hardened
+    /// derivation, so callers always pass a plain child index and this
+    /// function applies the `+ 2^31` offset itself.
+    pub fn derive_hardened(&self, index: u32) -> Result<Self, Error> {
+        if index >= HARDENED_OFFSET {
+            return Err(Error::SemanticError(
+                "ed25519 SLIP-0010 derivation only accepts unhardened indexes; \
+                 non-hardened derivation is not supported for this curve"
+                    .into(),
+            ));
+        }
+        let hardened_index = index + HARDENED_OFFSET;
+
+        let mut mac =
+            HmacSha512::new_varkey(&self.chain_code).expect("HMAC accepts any key size");
+        mac.update(&[0u8]);
+        mac.update(&self.priv_key);
+        mac.update(&hardened_index.to_be_bytes());
+        Ok(Self::from_hmac(mac))
+    }
+
+    /// Derive along a full hardened path starting from this key.
+    pub fn derive_path(&self, path: &[u32]) -> Result<Self, Error> {
+        path.iter()
+            .try_fold(self.clone(), |key, index| key.derive_hardened(*index))
+    }
+
+    fn from_hmac(mac: HmacSha512) -> Self {
+        let i = mac.finalize().into_bytes();
+        let mut priv_key = [0u8; 32];
+        let mut chain_code = [0u8; 32];
+        priv_key.copy_from_slice(&i[..32]);
+        chain_code.copy_from_slice(&i[32..]);
+        ExtendedKey {
+            priv_key,
+            chain_code,
+        }
+    }
+}
+
+/// Derive the Ed25519 private key seed at `path` (hardened-only) from a
+/// master `seed`.
+pub fn derive(seed: &[u8], path: &[u32]) -> Result<[u8; 32], Error> {
+    Ok(ExtendedKey::master(seed).derive_path(path)?.priv_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_master_is_deterministic() {
+        let seed = b"test seed material for slip-0010";
+        let a = ExtendedKey::master(seed);
+        let b = ExtendedKey::master(seed);
+        assert_eq!(a.priv_key, b.priv_key);
+        assert_eq!(a.chain_code, b.chain_code);
+    }
+
+    #[test]
+    fn test_derive_path_is_deterministic_and_path_sensitive() {
+        let seed = b"test seed material for slip-0010";
+        let a = derive(seed, &[0, 1]).unwrap();
+        let b = derive(seed, &[0, 1]).unwrap();
+        assert_eq!(a, b);
+
+        let c = derive(seed, &[0, 2]).unwrap();
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn test_index_already_in_hardened_range_rejected() {
+        let master = ExtendedKey::master(b"seed");
+        assert!(master.derive_hardened(HARDENED_OFFSET).is_err());
+        assert!(master.derive_hardened(HARDENED_OFFSET + 1).is_err());
+        assert!(master.derive_hardened(HARDENED_OFFSET - 1).is_ok());
+    }
+}
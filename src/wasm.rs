@@ -0,0 +1,60 @@
+//! `wasm-bindgen` bindings exposing the pure, LMDB-free parts of
+//! `event_message` (stream parsing and verification) to JS/browser KERI
+//! agents. Gated behind the `wasm` feature, following rs-ucan's pattern of a
+//! thin `wasm32` binding layer over an otherwise native crate.
+//!
+//! Everything here is a serde-serializable adapter: nom's byte-offset error
+//! tuples and `IdentifierState` are turned into `JsValue`s instead of being
+//! handed across the JS boundary as-is.
+
+use crate::event_message::parse::signed_event_stream_validate;
+use wasm_bindgen::prelude::*;
+
+/// Parse a raw KERI message stream into the list of events it contains.
+///
+/// Returns a JS array of the deserialized events on success, or throws a
+/// `JsValue` error describing where parsing failed.
+#[wasm_bindgen(js_name = parseEventStream)]
+pub fn parse_event_stream(stream: &[u8]) -> Result<JsValue, JsValue> {
+    let (_rest, events) = crate::event_message::parse::signed_event_stream(stream)
+        .map_err(|e| JsValue::from_str(&format!("failed to parse event stream: {:?}", e)))?;
+    JsValue::from_serde(&events.into_iter().map(WasmDeserialized::from).collect::<Vec<_>>())
+        .map_err(|e| JsValue::from_str(&format!("failed to serialize parsed stream: {}", e)))
+}
+
+/// Run `signed_event_stream_validate` over a raw KERI message stream and
+/// return the resulting `IdentifierState`.
+#[wasm_bindgen(js_name = validateEventStream)]
+pub fn validate_event_stream(stream: &[u8]) -> Result<JsValue, JsValue> {
+    let (_rest, state) = signed_event_stream_validate(stream)
+        .map_err(|e| JsValue::from_str(&format!("failed to validate event stream: {:?}", e)))?;
+    JsValue::from_serde(&state)
+        .map_err(|e| JsValue::from_str(&format!("failed to serialize identifier state: {}", e)))
+}
+
+/// `crate::event_message::parse::Deserialized` borrows the input stream's
+/// bytes, which doesn't survive the trip across the JS boundary. This is the
+/// owned, serde-serializable shape handed to JS instead.
+#[derive(serde::Serialize)]
+#[serde(tag = "type")]
+enum WasmDeserialized {
+    Event {
+        event: crate::event_message::EventMessage,
+        signatures: Vec<crate::prefix::AttachedSignaturePrefix>,
+    },
+    Vrc(crate::event_message::SignedEventMessage),
+    Rct(crate::event_message::SignedNontransferableReceipt),
+}
+
+impl From<crate::event_message::parse::Deserialized<'_>> for WasmDeserialized {
+    fn from(d: crate::event_message::parse::Deserialized) -> Self {
+        match d {
+            crate::event_message::parse::Deserialized::Event(e) => WasmDeserialized::Event {
+                event: e.event.event,
+                signatures: e.signatures,
+            },
+            crate::event_message::parse::Deserialized::Vrc(v) => WasmDeserialized::Vrc(v),
+            crate::event_message::parse::Deserialized::Rct(r) => WasmDeserialized::Rct(r),
+        }
+    }
+}